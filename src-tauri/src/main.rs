@@ -14,13 +14,12 @@
 
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Mutex;
 use tauri::{
-    AppHandle, Manager, PhysicalPosition, PhysicalSize, State, WebviewUrl, WebviewWindow,
-    WebviewWindowBuilder, Window, WindowEvent,
+    AppHandle, Manager, State, WebviewUrl, WebviewWindow, WebviewWindowBuilder, Window, WindowEvent,
 };
 
 mod commands;
@@ -36,11 +35,54 @@ use window::*;
 struct AppState {
     window_count: Mutex<usize>,
     fixed_windows: Mutex<HashMap<String, String>>,
+    /// Labels of currently-open windows, used to reject label collisions.
+    open_labels: Mutex<HashSet<String>>,
+    /// `scheme://host` origins allowed to drive privileged IPC commands.
+    /// Embedding contexts may extend this set at startup.
+    allowed_origins: Mutex<Vec<String>>,
+}
+
+impl AppState {
+    /// The default local asset origins Kui ships with.
+    fn default_allowed_origins() -> Vec<String> {
+        vec![
+            // macOS / Linux custom-protocol origin.
+            "tauri://localhost".to_string(),
+            // Windows serves over http, other platforms over https.
+            "https://tauri.localhost".to_string(),
+            "http://tauri.localhost".to_string(),
+            // Dev server (port is ignored — we compare scheme://host only).
+            "http://localhost".to_string(),
+        ]
+    }
+}
+
+/// Reject IPC from pages that have navigated away from the app's local asset
+/// origin. A remote page loaded inside a webview must not be able to quit the
+/// app or spawn shell windows, so `synchronous_message`, `create_new_window`
+/// and `exec_invoke` verify the invoking window's origin first.
+fn ensure_local_origin(window: &Window, state: &State<'_, AppState>) -> Result<(), String> {
+    let url = window
+        .url()
+        .map_err(|e| format!("Could not determine caller origin: {}", e))?;
+
+    let host = url.host_str().unwrap_or("");
+    let origin = format!("{}://{}", url.scheme(), host);
+
+    let allowed = state.allowed_origins.lock().unwrap();
+    if allowed.iter().any(|o| o == &origin) {
+        Ok(())
+    } else {
+        error!("Blocked IPC from disallowed origin: {}", origin);
+        Err(format!("Blocked IPC from disallowed origin: {}", origin))
+    }
 }
 
 /// Window preferences for subwindows
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct SubwindowPrefs {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     title: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -50,6 +92,8 @@ struct SubwindowPrefs {
     #[serde(skip_serializing_if = "Option::is_none")]
     fullscreen: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    visible_on_all_workspaces: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     initial_tab_title: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     quiet_exec_command: Option<bool>,
@@ -70,9 +114,36 @@ fn create_window_internal(
     argv: Option<Vec<String>>,
     prefs: Option<SubwindowPrefs>,
 ) -> tauri::Result<WebviewWindow> {
+    // Resolve the window label: use an explicit, validated label when the
+    // caller supplied one, otherwise fall back to the auto-incrementing
+    // `main-N` scheme. Either way the label must be unique among open windows.
     let mut count = state.window_count.lock().unwrap();
     *count += 1;
-    let window_label = format!("main-{}", *count);
+
+    let window_label = match prefs.as_ref().and_then(|p| p.label.clone()) {
+        Some(label) => {
+            if let Err(e) = validate_label(&label) {
+                *count -= 1;
+                return Err(tauri::Error::Io(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    e,
+                )));
+            }
+            label
+        }
+        None => format!("main-{}", *count),
+    };
+
+    {
+        let mut open = state.open_labels.lock().unwrap();
+        if !open.insert(window_label.clone()) {
+            *count -= 1;
+            return Err(tauri::Error::Io(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                LabelError::Collision(window_label),
+            )));
+        }
+    }
 
     let default_width = 1280;
     let default_height = 960;
@@ -118,12 +189,44 @@ fn create_window_internal(
         format!("index.html?{}", query_params.join("&"))
     };
 
-    let window = WebviewWindowBuilder::new(app, &window_label, WebviewUrl::App(url.into()))
+    let fullscreen = prefs.as_ref().and_then(|p| p.fullscreen).unwrap_or(false);
+    let visible_on_all_workspaces = prefs
+        .as_ref()
+        .and_then(|p| p.visible_on_all_workspaces)
+        .unwrap_or(false);
+
+    let window = match WebviewWindowBuilder::new(app, &window_label, WebviewUrl::App(url.into()))
         .title(title)
         .inner_size(width as f64, height as f64)
         .center()
         .resizable(true)
-        .build()?;
+        .fullscreen(fullscreen)
+        .visible_on_all_workspaces(visible_on_all_workspaces)
+        .build()
+    {
+        Ok(window) => window,
+        Err(e) => {
+            // Roll back the bookkeeping we reserved for this label.
+            state.open_labels.lock().unwrap().remove(&window_label);
+            *count -= 1;
+            return Err(e);
+        }
+    };
+
+    // Restore any saved geometry for this label. Attributes that the caller
+    // explicitly overrode via SubwindowPrefs are left untouched.
+    let mut flags = StateFlags::all();
+    if let Some(ref p) = prefs {
+        if p.width.is_some() || p.height.is_some() {
+            flags.remove(StateFlags::SIZE);
+        }
+        if p.fullscreen.is_some() {
+            flags.remove(StateFlags::FULLSCREEN);
+        }
+    }
+    if let Err(e) = restore_window_state(&window, flags) {
+        debug!("Could not restore window state for {}: {}", window_label, e);
+    }
 
     Ok(window)
 }
@@ -137,12 +240,20 @@ async fn create_new_window(
     width: Option<u32>,
     height: Option<u32>,
     title: Option<String>,
+    label: Option<String>,
+    fullscreen: Option<bool>,
+    visible_on_all_workspaces: Option<bool>,
+    window: Window,
 ) -> Result<(), String> {
+    ensure_local_origin(&window, &state)?;
+
     let prefs = Some(SubwindowPrefs {
+        label,
         title,
         width,
         height,
-        fullscreen: None,
+        fullscreen,
+        visible_on_all_workspaces,
         initial_tab_title: None,
         quiet_exec_command: None,
     });
@@ -161,6 +272,8 @@ async fn synchronous_message(
     state: State<'_, AppState>,
     message: String,
 ) -> Result<String, String> {
+    ensure_local_origin(&window, &state)?;
+
     let msg: SynchronousMessage =
         serde_json::from_str(&message).map_err(|e| format!("Invalid message: {}", e))?;
 
@@ -191,12 +304,24 @@ async fn synchronous_message(
                 .get("title")
                 .and_then(|v| v.as_str())
                 .map(String::from);
+            let label = msg
+                .data
+                .get("label")
+                .and_then(|v| v.as_str())
+                .map(String::from);
+            let fullscreen = msg.data.get("fullscreen").and_then(|v| v.as_bool());
+            let visible_on_all_workspaces = msg
+                .data
+                .get("visibleOnAllWorkspaces")
+                .and_then(|v| v.as_bool());
 
             let prefs = Some(SubwindowPrefs {
+                label,
                 title,
                 width,
                 height,
-                fullscreen: None,
+                fullscreen,
+                visible_on_all_workspaces,
                 initial_tab_title: None,
                 quiet_exec_command: None,
             });
@@ -211,40 +336,141 @@ async fn synchronous_message(
                 .map_err(|e| format!("Failed to create shell window: {}", e))?;
             Ok("ok".to_string())
         }
-        "enlarge-window" => {
-            window
-                .set_size(PhysicalSize::new(1400, 1050))
-                .map_err(|e| format!("Failed to enlarge window: {}", e))?;
+        "enlarge-window" | "reduce-window" | "maximize-window" | "unmaximize-window" => {
+            let webview = app
+                .get_webview_window(window.label())
+                .ok_or_else(|| "Invoking window no longer exists".to_string())?;
+            resize_window(&webview, &msg.operation)
+                .map_err(|e| format!("Failed to {}: {}", msg.operation, e))?;
             Ok("ok".to_string())
         }
-        "reduce-window" => {
-            window
-                .set_size(PhysicalSize::new(1024, 768))
-                .map_err(|e| format!("Failed to reduce window: {}", e))?;
-            Ok("ok".to_string())
-        }
-        "maximize-window" => {
-            window
-                .maximize()
-                .map_err(|e| format!("Failed to maximize window: {}", e))?;
+        "set-fullscreen" => {
+            let target = resolve_target_window(&app, &window, &msg.data)?;
+            let value = msg
+                .data
+                .get("value")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            target
+                .set_fullscreen(value)
+                .map_err(|e| format!("Failed to set fullscreen: {}", e))?;
             Ok("ok".to_string())
         }
-        "unmaximize-window" => {
-            window
-                .unmaximize()
-                .map_err(|e| format!("Failed to unmaximize window: {}", e))?;
+        "set-visible-on-all-workspaces" => {
+            let target = resolve_target_window(&app, &window, &msg.data)?;
+            let value = msg
+                .data
+                .get("value")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            target
+                .set_visible_on_all_workspaces(value)
+                .map_err(|e| format!("Failed to set visible-on-all-workspaces: {}", e))?;
             Ok("ok".to_string())
         }
         _ => Err(format!("Unknown operation: {}", msg.operation)),
     }
 }
 
+/// Resolve the window an IPC operation should act on: the window named by an
+/// explicit `label` field when present, otherwise the invoking window.
+fn resolve_target_window(
+    app: &AppHandle,
+    invoking: &Window,
+    data: &serde_json::Value,
+) -> Result<WebviewWindow, String> {
+    let label = data
+        .get("label")
+        .and_then(|v| v.as_str())
+        .unwrap_or_else(|| invoking.label());
+    app.get_webview_window(label)
+        .ok_or_else(|| format!("No such window: {}", label))
+}
+
+/// Dispatch a native-menu selection to the same operations handled by
+/// `synchronous_message`, so menu-triggered and renderer-triggered actions stay
+/// in lockstep.
+fn handle_menu_event(app: &AppHandle, id: &str) -> Result<(), String> {
+    debug!("Menu event: {}", id);
+    let state = app.state::<AppState>();
+
+    match id {
+        menu::NEW_WINDOW => {
+            create_window_internal(app, &state, None, None)
+                .map_err(|e| format!("Failed to create window: {}", e))?;
+        }
+        menu::OPEN_GRAPHICAL_SHELL => {
+            create_window_internal(app, &state, Some(vec!["shell".to_string()]), None)
+                .map_err(|e| format!("Failed to create shell window: {}", e))?;
+        }
+        menu::ENLARGE_WINDOW
+        | menu::REDUCE_WINDOW
+        | menu::MAXIMIZE_WINDOW
+        | menu::UNMAXIMIZE_WINDOW => {
+            let focused = app
+                .webview_windows()
+                .into_values()
+                .find(|w| w.is_focused().unwrap_or(false));
+            if let Some(window) = focused {
+                resize_window(&window, id).map_err(|e| format!("Failed to {}: {}", id, e))?;
+            }
+        }
+        menu::QUIT => app.exit(0),
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Tauri command: Enable/disable or relabel a menu item at runtime, so plugins
+/// can contribute to and adjust the menu dynamically.
+#[tauri::command]
+async fn set_menu_item(
+    app: AppHandle,
+    id: String,
+    enabled: Option<bool>,
+    label: Option<String>,
+) -> Result<(), String> {
+    let menu = app
+        .menu()
+        .ok_or_else(|| "No application menu is installed".to_string())?;
+    // `Menu::get` only looks at top-level entries; the adjustable items all
+    // live one level down, inside the File/Window/View/Help submenus.
+    let item = menu
+        .get(&id)
+        .or_else(|| {
+            menu.items()
+                .ok()?
+                .into_iter()
+                .find_map(|kind| kind.as_submenu().and_then(|submenu| submenu.get(&id)))
+        })
+        .ok_or_else(|| format!("No such menu item: {}", id))?;
+
+    let item = item
+        .as_menuitem()
+        .ok_or_else(|| format!("Menu item {} is not adjustable", id))?;
+
+    if let Some(enabled) = enabled {
+        item.set_enabled(enabled)
+            .map_err(|e| format!("Failed to set enabled state: {}", e))?;
+    }
+    if let Some(label) = label {
+        item.set_text(label)
+            .map_err(|e| format!("Failed to relabel menu item: {}", e))?;
+    }
+
+    Ok(())
+}
+
 /// Tauri command: Execute plugin code in main process
 #[tauri::command]
 async fn exec_invoke(
-    app: AppHandle,
+    window: Window,
+    state: State<'_, AppState>,
     message: String,
 ) -> Result<serde_json::Value, String> {
+    ensure_local_origin(&window, &state)?;
+
     debug!("Received exec invoke: {}", message);
 
     // Parse the message
@@ -259,6 +485,34 @@ async fn exec_invoke(
     }))
 }
 
+/// Resolve the OS-level window id for a webview window, used to match the
+/// correct native surface when capturing pixels. Matching on the raw handle
+/// avoids the ambiguity of identical window titles.
+fn native_window_id(window: &WebviewWindow) -> Result<u64, String> {
+    use raw_window_handle::{HasWindowHandle, RawWindowHandle};
+
+    let handle = window
+        .window_handle()
+        .map_err(|e| format!("Could not read window handle: {}", e))?;
+
+    match handle.as_raw() {
+        #[cfg(target_os = "windows")]
+        RawWindowHandle::Win32(h) => Ok(isize::from(h.hwnd) as u64),
+        #[cfg(target_os = "linux")]
+        RawWindowHandle::Xlib(h) => Ok(h.window as u64),
+        #[cfg(target_os = "linux")]
+        RawWindowHandle::Xcb(h) => Ok(u32::from(h.window) as u64),
+        #[cfg(target_os = "macos")]
+        RawWindowHandle::AppKit(_) => {
+            Err("Window capture is not supported on macOS".to_string())
+        }
+        other => Err(format!(
+            "Window capture is not supported for handle type {:?}",
+            other
+        )),
+    }
+}
+
 /// Tauri command: Capture page to clipboard
 #[tauri::command]
 async fn capture_to_clipboard(
@@ -268,12 +522,84 @@ async fn capture_to_clipboard(
     width: u32,
     height: u32,
 ) -> Result<(), String> {
-    // Note: Screenshot functionality requires platform-specific implementation
-    // This is a placeholder for the actual implementation
+    use tauri::image::Image;
+    use tauri_plugin_clipboard_manager::ClipboardExt;
+
     info!(
         "Screenshot requested: x={}, y={}, width={}, height={}",
         x, y, width, height
     );
+
+    if width == 0 || height == 0 {
+        return Err("Requested capture region has zero area".to_string());
+    }
+
+    // The renderer passes the rectangle in logical (CSS) pixels; every pixel
+    // buffer we work with below is in physical pixels, so convert up front.
+    let scale = window
+        .scale_factor()
+        .map_err(|e| format!("Could not read scale factor: {}", e))?;
+    let px = (x as f64 * scale).round() as i32;
+    let py = (y as f64 * scale).round() as i32;
+    let pw = (width as f64 * scale).round() as u32;
+    let ph = (height as f64 * scale).round() as u32;
+
+    // Reject regions that fall outside the window's content area (physical px).
+    let inner = window
+        .inner_size()
+        .map_err(|e| format!("Could not read window size: {}", e))?;
+    if px < 0
+        || py < 0
+        || px as u32 + pw > inner.width
+        || py as u32 + ph > inner.height
+    {
+        return Err(format!(
+            "Requested region {}x{} at ({}, {}) lies outside window bounds {}x{}",
+            width, height, x, y, inner.width, inner.height
+        ));
+    }
+
+    // Capture Kui's own window surface rather than the desktop at a screen
+    // location, so overlapping windows from other applications never leak into
+    // the screenshot. Match the native surface by OS window id — titles default
+    // to "Kui" and would match an arbitrary window when several are open.
+    let webview = window
+        .app_handle()
+        .get_webview_window(window.label())
+        .ok_or_else(|| "Invoking window no longer exists".to_string())?;
+    let native_id = native_window_id(&webview)?;
+    let surface = xcap::Window::all()
+        .map_err(|e| format!("Could not enumerate windows: {}", e))?
+        .into_iter()
+        .find(|w| w.id() as u64 == native_id)
+        .ok_or_else(|| "Could not locate Kui window surface for capture".to_string())?;
+
+    let captured = surface
+        .capture_image()
+        .map_err(|e| format!("Could not capture window pixels: {}", e))?;
+
+    // The captured surface is frame-relative and includes window decorations,
+    // while the requested rect is content-area relative. Offset the crop by the
+    // decoration inset (inner minus outer origin) so the titlebar is excluded.
+    let outer = window
+        .outer_position()
+        .map_err(|e| format!("Could not read window position: {}", e))?;
+    let inner_pos = window
+        .inner_position()
+        .map_err(|e| format!("Could not read window position: {}", e))?;
+    let crop_x = (inner_pos.x - outer.x + px) as u32;
+    let crop_y = (inner_pos.y - outer.y + py) as u32;
+
+    // Crop the captured window surface down to the requested rectangle.
+    let cropped = image::imageops::crop_imm(&captured, crop_x, crop_y, pw, ph).to_image();
+
+    let image = Image::new_owned(cropped.into_raw(), pw, ph);
+    window
+        .app_handle()
+        .clipboard()
+        .write_image(&image)
+        .map_err(|e| format!("Could not write image to clipboard: {}", e))?;
+
     Ok(())
 }
 
@@ -287,10 +613,23 @@ fn main() {
         .manage(AppState {
             window_count: Mutex::new(0),
             fixed_windows: Mutex::new(HashMap::new()),
+            open_labels: Mutex::new(HashSet::new()),
+            allowed_origins: Mutex::new(AppState::default_allowed_origins()),
         })
         .setup(|app| {
             info!("Kui starting up...");
 
+            // Install the native application menu and route its events to the
+            // same operations the renderer drives over IPC.
+            let handle = app.handle().clone();
+            let menu = menu::create_menu(&handle)?;
+            app.set_menu(menu)?;
+            app.on_menu_event(move |app, event| {
+                if let Err(e) = handle_menu_event(app, event.id().as_ref()) {
+                    error!("Menu event {} failed: {}", event.id().as_ref(), e);
+                }
+            });
+
             // Create the initial window
             let state = app.state::<AppState>();
             create_window_internal(&app.handle(), &state, Some(vec!["shell".to_string()]), None)?;
@@ -299,7 +638,17 @@ fn main() {
         })
         .on_window_event(|window, event| {
             if let WindowEvent::CloseRequested { .. } = event {
+                // Persist the closing window's geometry so it reopens where the
+                // user left it on the next launch. Windows with an auto-generated
+                // `main-N` label are skipped (see `is_persistent_label`).
+                if let Some(webview) = window.app_handle().get_webview_window(window.label()) {
+                    if let Err(e) = save_window_state(&window.app_handle(), &webview) {
+                        warn!("Could not save window state for {}: {}", window.label(), e);
+                    }
+                }
+
                 let state = window.state::<AppState>();
+                state.open_labels.lock().unwrap().remove(window.label());
                 let mut count = state.window_count.lock().unwrap();
                 if *count > 0 {
                     *count -= 1;
@@ -312,6 +661,7 @@ fn main() {
             synchronous_message,
             exec_invoke,
             capture_to_clipboard,
+            set_menu_item,
         ])
         .run(tauri::generate_context!())
         .expect("error while running Kui application");
@@ -12,19 +12,80 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-//! Application menu management for Kui
+//! Application menu management for Kui.
+//!
+//! The menu items carry ids that match the operation names handled by
+//! `synchronous_message`, so a menu-triggered action and a renderer-triggered
+//! IPC call run through exactly the same code paths.
 
 use log::debug;
-use tauri::{AppHandle, Menu};
+use tauri::menu::{Menu, MenuItemBuilder, PredefinedMenuItem, SubmenuBuilder};
+use tauri::{AppHandle, Wry};
 
-/// Create and install application menus
-#[allow(dead_code)]
-pub fn create_menu(_app: &AppHandle) -> tauri::Result<Menu> {
+/// Menu item ids. These deliberately mirror the operation strings accepted by
+/// `synchronous_message` so both entry points dispatch identically.
+pub const NEW_WINDOW: &str = "new-window";
+pub const OPEN_GRAPHICAL_SHELL: &str = "open-graphical-shell";
+pub const ENLARGE_WINDOW: &str = "enlarge-window";
+pub const REDUCE_WINDOW: &str = "reduce-window";
+pub const MAXIMIZE_WINDOW: &str = "maximize-window";
+pub const UNMAXIMIZE_WINDOW: &str = "unmaximize-window";
+pub const QUIT: &str = "quit";
+
+/// Build the full application menu tree (File, Window, View, Help).
+pub fn create_menu(app: &AppHandle) -> tauri::Result<Menu<Wry>> {
     debug!("Creating application menu");
 
-    // Menu creation would go here
-    // For now, returning a default/empty menu
-    Menu::new()
+    let new_window = MenuItemBuilder::with_id(NEW_WINDOW, "New Window")
+        .accelerator("CmdOrCtrl+N")
+        .build(app)?;
+    let graphical_shell = MenuItemBuilder::with_id(OPEN_GRAPHICAL_SHELL, "Open Graphical Shell")
+        .accelerator("CmdOrCtrl+Shift+N")
+        .build(app)?;
+    let quit = MenuItemBuilder::with_id(QUIT, "Quit")
+        .accelerator("CmdOrCtrl+Q")
+        .build(app)?;
+
+    let file = SubmenuBuilder::new(app, "File")
+        .item(&new_window)
+        .item(&graphical_shell)
+        .separator()
+        .item(&quit)
+        .build()?;
+
+    let enlarge = MenuItemBuilder::with_id(ENLARGE_WINDOW, "Enlarge Window")
+        .accelerator("CmdOrCtrl+Plus")
+        .build(app)?;
+    let reduce = MenuItemBuilder::with_id(REDUCE_WINDOW, "Reduce Window")
+        .accelerator("CmdOrCtrl+-")
+        .build(app)?;
+    let maximize = MenuItemBuilder::with_id(MAXIMIZE_WINDOW, "Maximize Window")
+        .accelerator("CmdOrCtrl+Shift+Up")
+        .build(app)?;
+    let unmaximize = MenuItemBuilder::with_id(UNMAXIMIZE_WINDOW, "Unmaximize Window")
+        .accelerator("CmdOrCtrl+Shift+Down")
+        .build(app)?;
+
+    let window = SubmenuBuilder::new(app, "Window")
+        .item(&enlarge)
+        .item(&reduce)
+        .separator()
+        .item(&maximize)
+        .item(&unmaximize)
+        .separator()
+        .item(&PredefinedMenuItem::minimize(app, None)?)
+        .item(&PredefinedMenuItem::close_window(app, None)?)
+        .build()?;
+
+    let view = SubmenuBuilder::new(app, "View")
+        .item(&PredefinedMenuItem::fullscreen(app, None)?)
+        .build()?;
+
+    let help = SubmenuBuilder::new(app, "Help")
+        .item(&PredefinedMenuItem::about(app, None, None)?)
+        .build()?;
+
+    Menu::with_items(app, &[&file, &window, &view, &help])
 }
 
 /// Initialize menu subsystem
@@ -0,0 +1,288 @@
+// Copyright 2025 The Kubernetes Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Window geometry persistence for Kui.
+//!
+//! Remembers the size, position, maximized and fullscreen state of each window
+//! opened with an explicit label between launches, so such subwindows reopen
+//! where the user left them; auto-labelled `main-N` windows are not saved. Saved
+//! attributes are described by a [`StateFlags`] bitset, letting callers opt
+//! into exactly which parts of the geometry are restored.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+use bitflags::bitflags;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, PhysicalPosition, PhysicalSize, WebviewWindow};
+
+bitflags! {
+    /// Which attributes of a window's geometry should be saved and restored.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct StateFlags: u32 {
+        const SIZE = 1 << 0;
+        const POSITION = 1 << 1;
+        const MAXIMIZED = 1 << 2;
+        const FULLSCREEN = 1 << 3;
+        const VISIBLE = 1 << 4;
+    }
+}
+
+impl Default for StateFlags {
+    fn default() -> Self {
+        StateFlags::all()
+    }
+}
+
+/// Why a proposed window label was rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LabelError {
+    /// The label contained characters outside the allowed set.
+    Invalid(String),
+    /// A window with this label is already open.
+    Collision(String),
+}
+
+impl fmt::Display for LabelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LabelError::Invalid(label) => write!(
+                f,
+                "invalid window label {:?}: only alphanumerics and the characters -/:_ are allowed",
+                label
+            ),
+            LabelError::Collision(label) => {
+                write!(f, "a window with label {:?} is already open", label)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LabelError {}
+
+/// Validate a window label against the character set accepted by the webview
+/// runtime: alphanumerics plus `-`, `/`, `:` and `_`. A label coming from a
+/// plugin or `argv` must pass this before it reaches `WebviewWindowBuilder`.
+pub fn validate_label(label: &str) -> Result<(), LabelError> {
+    if label.is_empty()
+        || !label
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '/' | ':' | '_'))
+    {
+        return Err(LabelError::Invalid(label.to_string()));
+    }
+    Ok(())
+}
+
+/// The persisted geometry of a single window, keyed by its label.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowState {
+    pub width: u32,
+    pub height: u32,
+    pub x: i32,
+    pub y: i32,
+    pub maximized: bool,
+    pub fullscreen: bool,
+    pub visible: bool,
+}
+
+/// Name of the file under the app-data directory that holds the state map.
+const STATE_FILENAME: &str = "window-state.json";
+
+/// Whether a window label is stable enough to key persisted geometry on.
+///
+/// Auto-generated `main-N` labels are renumbered and reused across runs (the
+/// counter decrements on close), so restoring by one would apply an unrelated
+/// window's geometry. Only explicitly-chosen labels are persisted.
+pub fn is_persistent_label(label: &str) -> bool {
+    match label.strip_prefix("main-") {
+        Some(rest) => !(!rest.is_empty() && rest.chars().all(|c| c.is_ascii_digit())),
+        None => true,
+    }
+}
+
+/// Resolve the path of the persisted window-state file, creating the parent
+/// app-data directory if necessary.
+fn state_path(app: &AppHandle) -> tauri::Result<PathBuf> {
+    let dir = app.path().app_data_dir()?;
+    if !dir.exists() {
+        fs::create_dir_all(&dir)?;
+    }
+    Ok(dir.join(STATE_FILENAME))
+}
+
+/// Load the saved `label -> WindowState` map, returning an empty map when no
+/// state has been persisted yet or the file cannot be parsed.
+fn load_states(app: &AppHandle) -> HashMap<String, WindowState> {
+    let path = match state_path(app) {
+        Ok(path) => path,
+        Err(e) => {
+            warn!("Could not resolve window-state path: {}", e);
+            return HashMap::new();
+        }
+    };
+
+    match fs::read(&path) {
+        Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+            warn!("Ignoring malformed window-state file: {}", e);
+            HashMap::new()
+        }),
+        Err(_) => HashMap::new(),
+    }
+}
+
+/// Serialize the `label -> WindowState` map back to the app-data directory.
+fn store_states(app: &AppHandle, states: &HashMap<String, WindowState>) -> tauri::Result<()> {
+    let path = state_path(app)?;
+    let bytes = serde_json::to_vec_pretty(states)
+        .map_err(|e| tauri::Error::Io(std::io::Error::new(std::io::ErrorKind::Other, e)))?;
+    fs::write(&path, bytes)?;
+    Ok(())
+}
+
+/// Capture the closing window's current geometry and persist it, keyed by the
+/// window label, into the saved state map.
+pub fn save_window_state(app: &AppHandle, window: &WebviewWindow) -> tauri::Result<()> {
+    if !is_persistent_label(window.label()) {
+        return Ok(());
+    }
+
+    let scale = window.scale_factor().unwrap_or(1.0);
+    let maximized = window.is_maximized().unwrap_or(false);
+    let fullscreen = window.is_fullscreen().unwrap_or(false);
+    let visible = window.is_visible().unwrap_or(true);
+
+    // Save the outer-frame position, since restore applies it with
+    // `set_position`, which moves the outer frame. Reading and writing the same
+    // reference frame avoids drifting the content down by the titlebar height on
+    // every restart. Size uses the inner (content) area to match `set_size`.
+    let size: PhysicalSize<u32> = window.inner_size()?;
+    let position: PhysicalPosition<i32> = window.outer_position()?;
+
+    let state = WindowState {
+        width: size.width,
+        height: size.height,
+        x: position.x,
+        y: position.y,
+        maximized,
+        fullscreen,
+        visible,
+    };
+
+    debug!(
+        "Saving window state for {}: {:?} (scale {})",
+        window.label(),
+        state,
+        scale
+    );
+
+    let mut states = load_states(app);
+    states.insert(window.label().to_string(), state);
+    store_states(app, &states)
+}
+
+/// Apply any saved geometry for `window`, honouring the requested
+/// [`StateFlags`]. Off-screen positions are clamped to the available monitor
+/// bounds before being applied. Does nothing when no state was saved for the
+/// window's label.
+pub fn restore_window_state(
+    window: &WebviewWindow,
+    flags: StateFlags,
+) -> tauri::Result<()> {
+    if !is_persistent_label(window.label()) {
+        return Ok(());
+    }
+
+    let states = load_states(&window.app_handle());
+    let Some(state) = states.get(window.label()) else {
+        return Ok(());
+    };
+
+    debug!("Restoring window state for {}: {:?}", window.label(), state);
+
+    if flags.contains(StateFlags::SIZE) {
+        window.set_size(PhysicalSize::new(state.width, state.height))?;
+    }
+
+    if flags.contains(StateFlags::POSITION) {
+        let (x, y) = clamp_to_monitor(window, state.x, state.y, state.width, state.height);
+        window.set_position(PhysicalPosition::new(x, y))?;
+    }
+
+    if flags.contains(StateFlags::MAXIMIZED) && state.maximized {
+        window.maximize()?;
+    }
+
+    if flags.contains(StateFlags::FULLSCREEN) {
+        window.set_fullscreen(state.fullscreen)?;
+    }
+
+    if flags.contains(StateFlags::VISIBLE) && !state.visible {
+        window.hide()?;
+    }
+
+    Ok(())
+}
+
+/// Apply a named window-resize operation. Shared by the `synchronous_message`
+/// IPC path and the native menu so both behave identically. Unknown operations
+/// are a no-op.
+pub fn resize_window(window: &WebviewWindow, op: &str) -> tauri::Result<()> {
+    match op {
+        "enlarge-window" => window.set_size(PhysicalSize::new(1400, 1050)),
+        "reduce-window" => window.set_size(PhysicalSize::new(1024, 768)),
+        "maximize-window" => window.maximize(),
+        "unmaximize-window" => window.unmaximize(),
+        _ => Ok(()),
+    }
+}
+
+/// Clamp a saved top-left position so the window stays on a connected monitor,
+/// guarding against displays that were removed while the app was closed.
+fn clamp_to_monitor(
+    window: &WebviewWindow,
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+) -> (i32, i32) {
+    let monitors = window.available_monitors().unwrap_or_default();
+    if monitors.is_empty() {
+        return (x, y);
+    }
+
+    // Keep the position if it already lands inside any monitor's bounds.
+    let on_screen = monitors.iter().any(|m| {
+        let pos = m.position();
+        let size = m.size();
+        x >= pos.x
+            && y >= pos.y
+            && x < pos.x + size.width as i32
+            && y < pos.y + size.height as i32
+    });
+    if on_screen {
+        return (x, y);
+    }
+
+    // Otherwise clamp into the bounds of the first monitor in the list.
+    let m = &monitors[0];
+    let pos = m.position();
+    let size = m.size();
+    let max_x = pos.x + (size.width as i32 - width as i32).max(0);
+    let max_y = pos.y + (size.height as i32 - height as i32).max(0);
+    (x.clamp(pos.x, max_x), y.clamp(pos.y, max_y))
+}